@@ -2,41 +2,80 @@ use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque; // queue
+use std::hash::Hash;
+use std::io::{self, Read};
+use std::iter::Enumerate;
 
 // TODO: Visualize the graph with graphiz
-// TODO: Implement a variant that can search a text with just a immutable borrow (currently we need
-// &mut self).
 // TODO: Maybe provide a few IntoIter impls.
 
-pub struct AhoCorasick {
-    keywords: Vec<String>,
-    states: Vec<HashMap<char, usize>>,
+/// Controls which matches `AhoCorasick` reports when keywords overlap.
+///
+/// These follow the semantics of the `aho-corasick` crate:
+///
+/// - `Standard`: report every match, including overlapping ones.
+/// - `NonOverlapping`: report matches left to right, skipping any match that starts before the
+///   end of the previously reported match.
+/// - `LeftmostFirst`: report non-overlapping matches, each time taking the match that starts
+///   earliest (even if a later-starting one ends first); when several matches start at the
+///   same position, prefer the keyword that was added first.
+/// - `LeftmostLongest`: like `LeftmostFirst`, but when several matches start at the same
+///   position, prefer the longest one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    Standard,
+    NonOverlapping,
+    LeftmostFirst,
+    LeftmostLongest,
+}
+
+impl Default for MatchKind {
+    fn default() -> MatchKind {
+        MatchKind::Standard
+    }
+}
+
+/// Builder for an Aho-Corasick automaton over an alphabet `C` (e.g. `u8` for byte strings, or
+/// `char` via the `StrAhoCorasick` convenience wrapper below). Add keywords with `add_keyword`,
+/// then call `finalize` to get a `CompiledAhoCorasick` that can actually search text.
+pub struct AhoCorasick<C: Eq + Hash + Clone> {
+    keywords: Vec<Vec<C>>,
+    states: Vec<HashMap<C, usize>>,
 
-    // NOTE For state N fails state is fails[N-1]. Fail for state 0 is not defined.
-    fails: Option<Vec<usize>>,
+    // depths[n] = length (in units of C) of the path from the root to state n
+    depths: Vec<usize>,
 
     // outputs[n] = outputs of state n
     outputs: Vec<HashSet<usize>>,
+
+    match_kind: MatchKind,
 }
 
-impl AhoCorasick {
-    pub fn new() -> AhoCorasick {
+impl<C: Eq + Hash + Clone> AhoCorasick<C> {
+    pub fn new() -> AhoCorasick<C> {
+        AhoCorasick::with_match_kind(MatchKind::Standard)
+    }
+
+    pub fn with_match_kind(match_kind: MatchKind) -> AhoCorasick<C> {
         AhoCorasick {
             keywords: vec![],
             states: vec![HashMap::new()],
-            fails: None,
+            depths: vec![0],
             outputs: vec![HashSet::new()],
+            match_kind,
         }
     }
 
-    pub fn add_keyword(&mut self, s: &str) {
-        // TODO maybe provide a HashSet<String> API to avoid adding same keyword multiple times
-        self.keywords.push(s.to_owned());
+    pub fn add_keyword(&mut self, kw: &[C]) {
+        // TODO maybe provide a HashSet<Vec<C>> API to avoid adding same keyword multiple times
+        self.keywords.push(kw.to_vec());
 
         let mut state = 0;
-        for c in s.chars() {
+        let mut depth = 0;
+        for c in kw {
+            depth += 1;
             let n_states = self.states.len();
-            match self.states.get_mut(state).unwrap().entry(c) {
+            match self.states.get_mut(state).unwrap().entry(c.clone()) {
                 Entry::Occupied(entry) => {
                     state = entry.get().clone();
                 }
@@ -44,20 +83,99 @@ impl AhoCorasick {
                     entry.insert(n_states);
                     self.states.push(HashMap::new());
                     self.outputs.push(HashSet::new());
+                    self.depths.push(depth);
                     state = n_states;
                 }
             }
         }
 
         self.outputs[state].insert(self.keywords.len() - 1);
-        self.fails = None; // TODO can we update fails incrementally?
     }
 
-    fn make_fails(&mut self) {
-        if self.fails.is_some() {
-            return;
+    /// Compile the trie into a `CompiledAhoCorasick`: a goto table with one precomputed
+    /// transition per `(state, symbol class)`, so searches never need to follow fail links at
+    /// search time.
+    ///
+    /// Transitions are indexed by symbol *class* rather than by symbol directly: every symbol
+    /// that never appears on a trie edge behaves identically (it always falls back to the
+    /// root), so they're all lumped into a single "other" class (id 0). This keeps each state's
+    /// row small even over a byte alphabet with only a handful of keyword bytes actually used.
+    pub fn finalize(mut self) -> CompiledAhoCorasick<C> {
+        let fails = self.make_fails();
+
+        // Breadth-first order of the trie, root first. Fail links always point to a
+        // strictly shallower state, so processing states in this order guarantees that
+        // `goto[fails[s]]` is already complete by the time we compute `goto[s]`.
+        let bfs_order = self.bfs_order();
+
+        // Every symbol that appears on some edge of the trie. Symbols outside this set never
+        // distinguish any state from the root, so they all share class 0 and a lookup miss in
+        // `classes` defaults to it.
+        let alphabet: HashSet<C> = self
+            .states
+            .iter()
+            .flat_map(|edges| edges.keys().cloned())
+            .collect();
+
+        let classes: HashMap<C, u32> = alphabet
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, c)| (c, (i + 1) as u32))
+            .collect();
+        let n_classes = classes.len() + 1;
+
+        // goto[state][class] defaults to 0 (the root), which is correct for class 0 ("other")
+        // since an unseen-everywhere symbol always fails all the way back to the root from any
+        // state, by the same fail-chain-following logic `make_fails` already relies on.
+        let mut goto = vec![vec![0; n_classes]; self.states.len()];
+        for (state, edges) in self.states.iter().enumerate() {
+            for (c, dest) in edges {
+                goto[state][classes[c] as usize] = *dest;
+            }
+        }
+
+        for state in bfs_order {
+            if state == 0 {
+                continue; // the root has no fail state, and class 0 already defaults to it anyway
+            }
+            let fail_row = goto[fails[state - 1]].clone();
+            for (slot, fallback) in goto[state].iter_mut().zip(fail_row.iter()).skip(1) {
+                if *slot == 0 {
+                    *slot = *fallback;
+                }
+            }
         }
 
+        CompiledAhoCorasick {
+            keywords: self.keywords,
+            classes,
+            goto,
+            depths: self.depths,
+            outputs: self.outputs,
+            match_kind: self.match_kind,
+        }
+    }
+
+    fn bfs_order(&self) -> Vec<usize> {
+        let mut order = vec![0];
+        let mut work_list: VecDeque<usize> = VecDeque::new();
+        work_list.push_back(0);
+        while let Some(state) = work_list.pop_front() {
+            for next in self.states[state].values() {
+                order.push(*next);
+                work_list.push_back(*next);
+            }
+        }
+        order
+    }
+
+    // Computes, for every state but the root, the state to fall back to when no outgoing edge
+    // matches the next input symbol. Also merges each state's outputs with its fail state's
+    // outputs, so a match ending via a fail link is still reported.
+    //
+    // NOTE For state N the fail state is fails[N-1]. The root (state 0) has no fail state.
+    fn make_fails(&mut self) -> Vec<usize> {
         let mut fails = vec![0; self.states.len() - 1];
         // -1 because state 0 doesn't have a fail state
         // (perhaps define f(0) = 0 and simplify this?
@@ -70,7 +188,7 @@ impl AhoCorasick {
         // Start calculating from depth 1
         {
             let init_state = &self.states[0];
-            for (_ch, next) in init_state {
+            for next in init_state.values() {
                 work_list.push_back(*next);
                 assert!(*next != 0);
                 fails[*next - 1] = 0;
@@ -104,31 +222,149 @@ impl AhoCorasick {
             }
         }
 
-        self.fails = Some(fails);
+        fails
     }
+}
 
-    pub fn match_(&mut self, text: &str) -> Vec<(usize, &str)> {
-        let mut ret = vec![];
+/// A compiled Aho-Corasick automaton, ready to search text. Build one with `AhoCorasick` and
+/// `AhoCorasick::finalize`.
+///
+/// Unlike `AhoCorasick`, every method here takes `&self`: the goto table is fully precomputed,
+/// so searching never mutates the automaton, and a `CompiledAhoCorasick` can be shared across
+/// threads and searched concurrently.
+///
+/// Matches are reported as `(start, keyword_index)` pairs, where `start` is measured in units
+/// of `C` (e.g. true byte offsets when `C = u8`); use `keyword` to look up the matched keyword.
+pub struct CompiledAhoCorasick<C: Eq + Hash + Clone> {
+    keywords: Vec<Vec<C>>,
+
+    // Maps a symbol to its equivalence class; symbols absent from this map share class 0.
+    classes: HashMap<C, u32>,
+
+    // goto[state][class] = transition table, indexed by symbol class rather than by symbol.
+    goto: Vec<Vec<usize>>,
+
+    depths: Vec<usize>,
+    outputs: Vec<HashSet<usize>>,
+    match_kind: MatchKind,
+}
 
-        // Ideally make_fails() would return a reference to the fail vector but that causes
-        // borrowchk issues
-        self.make_fails();
-        let fails = self.fails.as_ref().unwrap();
+impl<C: Eq + Hash + Clone> CompiledAhoCorasick<C> {
+    pub fn keyword(&self, idx: usize) -> &[C] {
+        &self.keywords[idx]
+    }
 
+    fn step(&self, state: usize, c: &C) -> usize {
+        let class = self.classes.get(c).cloned().unwrap_or(0) as usize;
+        self.goto[state][class]
+    }
+
+    pub fn match_<I: Iterator<Item = C>>(&self, text: I) -> Vec<(usize, usize)> {
+        let units: Vec<C> = text.collect();
+
+        let mut ret = vec![];
         let mut state = 0;
-        for (ch_idx, ch) in text.chars().enumerate() {
-            while state != 0 && self.states[state].get(&ch).is_none() {
-                state = fails[state - 1];
+        let mut i = 0;
+
+        // Only used for `LeftmostFirst` / `LeftmostLongest`: the best candidate match seen so
+        // far that hasn't been committed yet.
+        let mut pending: Option<(usize, usize)> = None; // (start, keyword idx)
+
+        // Only used for `NonOverlapping`: one past the end of the last emitted match.
+        let mut last_end: Option<usize> = None;
+
+        'resume: loop {
+            while i < units.len() {
+                state = self.step(state, &units[i]);
+
+                if let Some((pending_start, _)) = pending {
+                    // Can the current state still extend into a match that begins at or before
+                    // `pending_start`? If not, we can no longer improve on `pending`, so commit it.
+                    let earliest_active_start = (i + 1).saturating_sub(self.depths[state]);
+                    if earliest_active_start > pending_start {
+                        let (commit_start, commit_kw) = pending.take().unwrap();
+                        ret.push((commit_start, commit_kw));
+
+                        // Resume scanning right after the committed match.
+                        state = 0;
+                        i = commit_start + self.keywords[commit_kw].len();
+                        continue;
+                    }
+                }
+
+                if self.match_kind == MatchKind::NonOverlapping {
+                    // Several outputs can end at the same position (e.g. one keyword is a suffix
+                    // of another, merged onto this state via a fail link); `self.outputs[state]` is
+                    // a `HashSet`, so its iteration order is unspecified. Any two outputs ending
+                    // here necessarily overlap each other (they share this last unit), so at most
+                    // one can be emitted; try them earliest-start first so the choice doesn't
+                    // depend on hash iteration order, falling back to a later start if the
+                    // earliest one overlaps the previously emitted match.
+                    let mut candidates: Vec<(usize, usize)> = self.outputs[state]
+                        .iter()
+                        .map(|&output| (i + 1 - self.keywords[output].len(), output))
+                        .collect();
+                    candidates.sort_unstable();
+
+                    if let Some(&(start, output)) = candidates
+                        .iter()
+                        .find(|&&(start, _)| last_end.is_none_or(|end| start >= end))
+                    {
+                        ret.push((start, output));
+                        last_end = Some(start + self.keywords[output].len());
+                    }
+                } else {
+                    for output in &self.outputs[state] {
+                        let kw_len = self.keywords[*output].len();
+                        let start = i + 1 - kw_len;
+
+                        match self.match_kind {
+                            MatchKind::Standard => {
+                                ret.push((start, *output));
+                            }
+
+                            MatchKind::NonOverlapping => unreachable!(),
+
+                            MatchKind::LeftmostFirst => match pending {
+                                None => pending = Some((start, *output)),
+                                Some((pending_start, pending_kw)) => {
+                                    if start < pending_start
+                                        || (start == pending_start && *output < pending_kw)
+                                    {
+                                        pending = Some((start, *output));
+                                    }
+                                }
+                            },
+
+                            MatchKind::LeftmostLongest => match pending {
+                                None => pending = Some((start, *output)),
+                                Some((pending_start, pending_kw)) => {
+                                    let pending_len = self.keywords[pending_kw].len();
+                                    if start < pending_start
+                                        || (start == pending_start && kw_len > pending_len)
+                                    {
+                                        pending = Some((start, *output));
+                                    }
+                                }
+                            },
+                        }
+                    }
+                }
+
+                i += 1;
             }
-            state = self.states[state].get(&ch).cloned().unwrap_or(0);
-
-            for output in &self.outputs[state] {
-                let kw = self.keywords[*output].as_str();
-                // println!("check_output idx: {}, state: {}, kw: {}", idx, state, kw);
-                ret.push((
-                    ch_idx - (kw.len() - 1), /* FIXME not correct for unicode */
-                    kw,
-                ));
+
+            // The text ended while still scanning for a better candidate: commit it and resume
+            // scanning the remainder exactly as the mid-loop commit above does, since there may
+            // still be further matches after it.
+            match pending.take() {
+                Some((commit_start, commit_kw)) => {
+                    ret.push((commit_start, commit_kw));
+                    state = 0;
+                    i = commit_start + self.keywords[commit_kw].len();
+                    continue 'resume;
+                }
+                None => break,
             }
         }
 
@@ -136,42 +372,34 @@ impl AhoCorasick {
     }
 }
 
-pub struct MatchIter<'a, 'b> {
-    ac: &'a AhoCorasick,
+pub struct StandardMatchIter<'a, C: Eq + Hash + Clone, I: Iterator<Item = C>> {
+    ac: &'a CompiledAhoCorasick<C>,
     state: usize,
     loc: usize,
-    chars: ::std::iter::Enumerate<::std::str::Chars<'b>>,
+    units: Enumerate<I>,
 
     // Only available when yielding outputs of a state
     output_iter: Option<::std::collections::hash_set::Iter<'a, usize>>,
 }
 
-impl<'a, 'b> Iterator for MatchIter<'a, 'b> {
-    type Item = (usize, &'a str);
+impl<'a, C: Eq + Hash + Clone, I: Iterator<Item = C>> Iterator for StandardMatchIter<'a, C, I> {
+    type Item = (usize, usize);
 
-    fn next(&mut self) -> Option<(usize, &'a str)> {
+    fn next(&mut self) -> Option<(usize, usize)> {
         if let Some(output_iter) = &mut self.output_iter {
             if let Some(output_idx) = output_iter.next() {
-                let kw = self.ac.keywords[*output_idx].as_str();
-                return Some((
-                        self.loc - (kw.len() - 1), // FIXME
-                        kw,
-                ));
+                let kw_len = self.ac.keywords[*output_idx].len();
+                return Some((self.loc + 1 - kw_len, *output_idx));
             } else {
                 self.output_iter = None;
             }
         }
 
-        match self.chars.next() {
+        match self.units.next() {
             None => None,
-            Some((ch_idx, ch)) => {
-                self.loc = ch_idx;
-                while self.state != 0 && self.ac.states[self.state].get(&ch).is_none() {
-                    // TODO: what if fails was invalidated? is that even possible?
-                    // (can I add a word after building an iterator?)
-                    self.state = (self.ac.fails.as_ref().unwrap())[self.state - 1];
-                }
-                self.state = self.ac.states[self.state].get(&ch).cloned().unwrap_or(0);
+            Some((idx, c)) => {
+                self.loc = idx;
+                self.state = self.ac.step(self.state, &c);
                 self.output_iter = Some(self.ac.outputs[self.state].iter());
                 self.next()
             }
@@ -179,29 +407,221 @@ impl<'a, 'b> Iterator for MatchIter<'a, 'b> {
     }
 }
 
-impl AhoCorasick {
-    pub fn match_iter<'a, 'b>(&'a mut self, str: &'b str) -> MatchIter<'a, 'b> {
-        self.make_fails();
-        MatchIter {
+/// The leftmost `match_kind`s need to look arbitrarily far ahead of a candidate match before
+/// deciding whether to commit it, so there's no streaming-friendly way to yield them lazily:
+/// for every kind other than `Standard`, `match_iter` computes the whole result up front and
+/// hands out an iterator over it.
+pub enum MatchIter<'a, C: Eq + Hash + Clone, I: Iterator<Item = C>> {
+    Standard(StandardMatchIter<'a, C, I>),
+    Precomputed(::std::vec::IntoIter<(usize, usize)>),
+}
+
+impl<'a, C: Eq + Hash + Clone, I: Iterator<Item = C>> Iterator for MatchIter<'a, C, I> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        match self {
+            MatchIter::Standard(iter) => iter.next(),
+            MatchIter::Precomputed(iter) => iter.next(),
+        }
+    }
+}
+
+impl<C: Eq + Hash + Clone> CompiledAhoCorasick<C> {
+    pub fn match_iter<'a, I: Iterator<Item = C>>(&'a self, units: I) -> MatchIter<'a, C, I> {
+        if self.match_kind != MatchKind::Standard {
+            return MatchIter::Precomputed(self.match_(units).into_iter());
+        }
+
+        MatchIter::Standard(StandardMatchIter {
+            ac: self,
+            state: 0,
+            loc: 0,
+            units: units.enumerate(),
+            output_iter: None,
+        })
+    }
+}
+
+// Chunk size used by `stream_match` to read from the underlying `Read`.
+const STREAM_BUF_SIZE: usize = 64 * 1024;
+
+impl CompiledAhoCorasick<u8> {
+    /// Search a `Read` stream without buffering the whole input in memory. The automaton's
+    /// current state is carried across chunk boundaries, so matches spanning two chunks are
+    /// still found; reported positions are absolute byte offsets from the start of the stream.
+    ///
+    /// Only `MatchKind::Standard` overlapping semantics are supported here; any other kind
+    /// panics. The leftmost kinds may need to rewind past already-consumed bytes to commit a
+    /// candidate match, which isn't possible on a forward-only stream.
+    pub fn stream_match<R: Read>(&self, r: R) -> AcStreamMatchIter<'_, R> {
+        assert_eq!(
+            self.match_kind,
+            MatchKind::Standard,
+            "stream_match only supports MatchKind::Standard"
+        );
+
+        AcStreamMatchIter {
             ac: self,
+            reader: r,
+            buf: vec![0; STREAM_BUF_SIZE],
+            buf_len: 0,
+            buf_pos: 0,
+            chunk_start: 0,
             state: 0,
             loc: 0,
-            chars: str.chars().enumerate(),
             output_iter: None,
+            done: false,
         }
     }
 }
 
+pub struct AcStreamMatchIter<'a, R: Read> {
+    ac: &'a CompiledAhoCorasick<u8>,
+    reader: R,
+    buf: Vec<u8>,
+    buf_len: usize,
+    buf_pos: usize,
+
+    // Absolute offset of buf[0] in the stream.
+    chunk_start: usize,
+
+    state: usize,
+    loc: usize,
+    output_iter: Option<::std::collections::hash_set::Iter<'a, usize>>,
+    done: bool,
+}
+
+impl<'a, R: Read> Iterator for AcStreamMatchIter<'a, R> {
+    type Item = io::Result<(usize, usize)>;
+
+    fn next(&mut self) -> Option<io::Result<(usize, usize)>> {
+        loop {
+            if let Some(output_iter) = &mut self.output_iter {
+                if let Some(output_idx) = output_iter.next() {
+                    let kw_len = self.ac.keywords[*output_idx].len();
+                    return Some(Ok((self.loc + 1 - kw_len, *output_idx)));
+                } else {
+                    self.output_iter = None;
+                }
+            }
+
+            if self.buf_pos >= self.buf_len {
+                if self.done {
+                    return None;
+                }
+
+                match self.reader.read(&mut self.buf) {
+                    Ok(0) => {
+                        self.done = true;
+                        return None;
+                    }
+                    Ok(n) => {
+                        self.chunk_start += self.buf_len;
+                        self.buf_len = n;
+                        self.buf_pos = 0;
+                    }
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                }
+                continue;
+            }
+
+            let byte = self.buf[self.buf_pos];
+            self.loc = self.chunk_start + self.buf_pos;
+            self.buf_pos += 1;
+            self.state = self.ac.step(self.state, &byte);
+            self.output_iter = Some(self.ac.outputs[self.state].iter());
+        }
+    }
+}
+
+/// Convenience wrapper around `AhoCorasick<char>` for matching `&str` keywords and text. Match
+/// positions are reported as char indices (not byte offsets) since the automaton operates on
+/// `char`s, not bytes.
+pub struct StrAhoCorasick {
+    keywords: Vec<String>,
+    inner: AhoCorasick<char>,
+}
+
+impl Default for StrAhoCorasick {
+    fn default() -> StrAhoCorasick {
+        StrAhoCorasick::new()
+    }
+}
+
+impl StrAhoCorasick {
+    pub fn new() -> StrAhoCorasick {
+        StrAhoCorasick::with_match_kind(MatchKind::Standard)
+    }
+
+    pub fn with_match_kind(match_kind: MatchKind) -> StrAhoCorasick {
+        StrAhoCorasick {
+            keywords: vec![],
+            inner: AhoCorasick::with_match_kind(match_kind),
+        }
+    }
+
+    pub fn add_keyword(&mut self, s: &str) {
+        self.keywords.push(s.to_owned());
+        let chars: Vec<char> = s.chars().collect();
+        self.inner.add_keyword(&chars);
+    }
+
+    pub fn finalize(self) -> CompiledStrAhoCorasick {
+        CompiledStrAhoCorasick {
+            keywords: self.keywords,
+            inner: self.inner.finalize(),
+        }
+    }
+}
+
+pub struct CompiledStrAhoCorasick {
+    keywords: Vec<String>,
+    inner: CompiledAhoCorasick<char>,
+}
+
+impl CompiledStrAhoCorasick {
+    pub fn match_(&self, text: &str) -> Vec<(usize, &str)> {
+        self.inner
+            .match_(text.chars())
+            .into_iter()
+            .map(|(start, kw_idx)| (start, self.keywords[kw_idx].as_str()))
+            .collect()
+    }
+
+    pub fn match_iter<'a, 'b>(&'a self, text: &'b str) -> StrMatchIter<'a, 'b> {
+        StrMatchIter {
+            keywords: &self.keywords,
+            inner: self.inner.match_iter(text.chars()),
+        }
+    }
+}
+
+pub struct StrMatchIter<'a, 'b> {
+    keywords: &'a [String],
+    inner: MatchIter<'a, char, ::std::str::Chars<'b>>,
+}
+
+impl<'a, 'b> Iterator for StrMatchIter<'a, 'b> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        self.inner
+            .next()
+            .map(|(start, kw_idx)| (start, self.keywords[kw_idx].as_str()))
+    }
+}
+
 #[test]
 fn test_trie() {
-    let mut ac = AhoCorasick::new();
+    let mut ac = StrAhoCorasick::new();
     ac.add_keyword("hers");
     ac.add_keyword("his");
     ac.add_keyword("she");
-
-    // println!("states: {:?}", ac.states);
-    // println!("fails: {:?}", ac.fails);
-    // println!("outputs: {:?}", ac.outputs);
+    let ac = ac.finalize();
 
     assert_eq!(ac.match_("she"), vec![(0, "she")]);
 
@@ -224,11 +644,12 @@ fn test_trie() {
 
 #[test]
 fn test_trie_2() {
-    let mut ac = AhoCorasick::new();
+    let mut ac = StrAhoCorasick::new();
     ac.add_keyword("fo");
     ac.add_keyword("xfoo");
     ac.add_keyword("bar");
     ac.add_keyword("bax");
+    let ac = ac.finalize();
 
     // We start matching "xfoo", but after "xfo" we fail, and fail state has an output.
     assert_eq!(ac.match_("xfobaxbar"), vec![(1, "fo"), (3, "bax"), (6, "bar")]);
@@ -236,10 +657,11 @@ fn test_trie_2() {
 
 #[test]
 fn test_trie_iterator() {
-    let mut ac = AhoCorasick::new();
+    let mut ac = StrAhoCorasick::new();
     ac.add_keyword("hers");
     ac.add_keyword("his");
     ac.add_keyword("she");
+    let ac = ac.finalize();
 
     let mut iter = ac.match_iter(" she hers his ");
     assert_eq!(iter.next(), Some((1, "she")));
@@ -253,11 +675,12 @@ fn test_trie_iterator() {
     assert_eq!(iter.next(), Some((2, "she")));
     assert_eq!(iter.next(), None);
 
-    let mut ac = AhoCorasick::new();
+    let mut ac = StrAhoCorasick::new();
     ac.add_keyword("fo");
     ac.add_keyword("xfoo");
     ac.add_keyword("bar");
     ac.add_keyword("bax");
+    let ac = ac.finalize();
 
     let mut iter = ac.match_iter("xfobaxbar");
     assert_eq!(iter.next(), Some((1, "fo")));
@@ -265,3 +688,176 @@ fn test_trie_iterator() {
     assert_eq!(iter.next(), Some((6, "bar")));
     assert_eq!(iter.next(), None);
 }
+
+#[test]
+fn test_match_kind_non_overlapping() {
+    let mut ac = StrAhoCorasick::with_match_kind(MatchKind::NonOverlapping);
+    ac.add_keyword("hers");
+    ac.add_keyword("his");
+    ac.add_keyword("she");
+    let ac = ac.finalize();
+
+    // "his" (0..3) and "she" (2..5) overlap; only the first one is kept.
+    assert_eq!(ac.match_("hishe"), vec![(0, "his")]);
+}
+
+#[test]
+fn test_match_kind_non_overlapping_same_end() {
+    // "cde" is a suffix of "abcde", so both outputs merge onto the same state via a fail
+    // link: they end at the same position but start at different ones. The choice between
+    // them must be deterministic rather than depend on `HashSet` iteration order.
+    let mut ac = StrAhoCorasick::with_match_kind(MatchKind::NonOverlapping);
+    ac.add_keyword("abcde");
+    ac.add_keyword("cde");
+    let ac = ac.finalize();
+
+    assert_eq!(ac.match_("xabcdey"), vec![(1, "abcde")]);
+}
+
+#[test]
+fn test_match_kind_non_overlapping_fallback_to_later_start() {
+    // "xa" (0..2) is emitted first, so "ab" (1..3) overlaps it and must be rejected; but "b"
+    // (2..3), which ends at the same position as "ab", doesn't overlap and should still be
+    // emitted instead of the whole position being dropped.
+    let mut ac = StrAhoCorasick::with_match_kind(MatchKind::NonOverlapping);
+    ac.add_keyword("xa");
+    ac.add_keyword("ab");
+    ac.add_keyword("b");
+    let ac = ac.finalize();
+
+    assert_eq!(ac.match_("xab"), vec![(0, "xa"), (2, "b")]);
+}
+
+#[test]
+fn test_match_kind_leftmost_first() {
+    // "a" is added before "ab", so on a tie `LeftmostFirst` keeps "a".
+    let mut ac = StrAhoCorasick::with_match_kind(MatchKind::LeftmostFirst);
+    ac.add_keyword("a");
+    ac.add_keyword("ab");
+    let ac = ac.finalize();
+
+    assert_eq!(ac.match_("ab"), vec![(0, "a")]);
+}
+
+#[test]
+fn test_match_kind_leftmost_longest() {
+    // Same automaton as above, but `LeftmostLongest` prefers "ab" on a tie.
+    let mut ac = StrAhoCorasick::with_match_kind(MatchKind::LeftmostLongest);
+    ac.add_keyword("a");
+    ac.add_keyword("ab");
+    let ac = ac.finalize();
+
+    assert_eq!(ac.match_("ab"), vec![(0, "ab")]);
+}
+
+#[test]
+fn test_match_kind_leftmost_first_flush_resumes_scan() {
+    // The second "ba" (at 4) is still pending when the text ends: the trailing "a" completes
+    // "baa", which loses to "ba" under `LeftmostFirst`, so "ba" is committed by the end-of-text
+    // flush. The flush must then resume scanning instead of stopping, or the final "a" match
+    // gets silently dropped.
+    let mut ac = StrAhoCorasick::with_match_kind(MatchKind::LeftmostFirst);
+    ac.add_keyword("ba");
+    ac.add_keyword("baa");
+    ac.add_keyword("a");
+    let ac = ac.finalize();
+
+    assert_eq!(
+        ac.match_("bbbabaa"),
+        vec![(2, "ba"), (4, "ba"), (6, "a")]
+    );
+}
+
+#[test]
+fn test_class_compression() {
+    // Only 6 distinct bytes appear across the keywords, so transitions should be indexed by a
+    // handful of classes rather than one row entry per byte of the alphabet.
+    let mut ac: AhoCorasick<u8> = AhoCorasick::new();
+    ac.add_keyword(b"fo");
+    ac.add_keyword(b"xfoo");
+    ac.add_keyword(b"bar");
+    ac.add_keyword(b"bax");
+    let ac = ac.finalize();
+
+    assert_eq!(ac.classes.len(), 6); // f, o, x, b, a, r
+    assert!(ac.goto.iter().all(|row| row.len() == 7)); // 6 classes + "other"
+
+    // Unaffected by the representation change: still reports the same matches.
+    let matches = ac.match_(b"xfobaxbar".iter().cloned());
+    assert_eq!(
+        matches
+            .into_iter()
+            .map(|(start, kw)| (start, ac.keyword(kw)))
+            .collect::<Vec<_>>(),
+        vec![(1, &b"fo"[..]), (3, &b"bax"[..]), (6, &b"bar"[..])]
+    );
+}
+
+#[test]
+fn test_compiled_is_sync() {
+    fn assert_sync<T: Sync>() {}
+    assert_sync::<CompiledAhoCorasick<char>>();
+    assert_sync::<CompiledAhoCorasick<u8>>();
+}
+
+#[test]
+fn test_bytes() {
+    // Byte-oriented matching reports true byte offsets, including for multi-byte UTF-8 text.
+    let mut ac: AhoCorasick<u8> = AhoCorasick::new();
+    ac.add_keyword(b"h\xc3\xa9"); // "h\u{e9}" ("h" + Latin small letter e with acute), UTF-8 encoded
+    ac.add_keyword(b"llo");
+    let ac = ac.finalize();
+
+    let text = "h\u{e9}llo".as_bytes(); // "héllo"
+    let matches = ac.match_(text.iter().cloned());
+
+    assert_eq!(matches.len(), 2);
+    assert_eq!((matches[0].0, ac.keyword(matches[0].1)), (0, &b"h\xc3\xa9"[..]));
+    assert_eq!((matches[1].0, ac.keyword(matches[1].1)), (3, &b"llo"[..]));
+}
+
+// A `Read` that only ever returns a few bytes per call, so tests can exercise matches that span
+// a chunk boundary without actually needing gigabytes of input.
+#[cfg(test)]
+struct TinyReads<'a>(&'a [u8]);
+
+#[cfg(test)]
+impl<'a> Read for TinyReads<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = 3.min(buf.len()).min(self.0.len());
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_stream_match() {
+    let mut ac: AhoCorasick<u8> = AhoCorasick::new();
+    ac.add_keyword(b"fo");
+    ac.add_keyword(b"xfoo");
+    ac.add_keyword(b"bar");
+    ac.add_keyword(b"bax");
+    // "oba" (index 2..5) straddles the boundary between `TinyReads`'s first two 3-byte
+    // chunks ("xfo" | "bax" | "bar"), so this test actually exercises a match spanning a
+    // chunk boundary, per the doc comment on `stream_match`.
+    ac.add_keyword(b"oba");
+    let ac = ac.finalize();
+
+    let text = b"xfobaxbar";
+    let matches: Vec<(usize, &[u8])> = ac
+        .stream_match(TinyReads(text))
+        .map(|m| m.map(|(start, kw_idx)| (start, ac.keyword(kw_idx))))
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+
+    assert_eq!(
+        matches,
+        vec![
+            (1, &b"fo"[..]),
+            (2, &b"oba"[..]),
+            (3, &b"bax"[..]),
+            (6, &b"bar"[..]),
+        ]
+    );
+}
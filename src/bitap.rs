@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::iter::Enumerate;
+
+// The whole match state is packed into a `u64` register, so patterns longer than this many
+// symbols aren't supported.
+const WORD_BITS: usize = 64;
+
+/// Shift-And (bitap) matcher over an alphabet `C`. Unlike `KMP`'s failure-function approach,
+/// bitap's per-symbol bitmasks make it easy to support a single wildcard position (see `new`)
+/// and k-mismatch approximate search (see `match_fuzzy`).
+///
+/// Patterns are limited to `WORD_BITS` symbols, since the whole match state is packed into one
+/// machine word; `new` panics if the pattern is longer than that.
+pub struct Bitap<C: Eq + Hash + Clone> {
+    pattern_len: usize,
+
+    // masks[c] = bitmask with bit j set iff pattern[j] == c, or pattern[j] is the wildcard.
+    // Symbols that don't appear in the pattern aren't in this map; their effective mask is
+    // `wildcard_bits`.
+    masks: HashMap<C, u64>,
+
+    // Bits set at wildcard positions. Every symbol's mask includes these bits, since a wildcard
+    // matches any symbol.
+    wildcard_bits: u64,
+}
+
+impl<C: Eq + Hash + Clone> Bitap<C> {
+    /// Build a matcher for `pattern`. If `wildcard` is `Some(c)`, every position of the pattern
+    /// equal to `c` matches any symbol in the text.
+    pub fn new(pattern: &[C], wildcard: Option<C>) -> Bitap<C> {
+        assert!(!pattern.is_empty());
+        assert!(
+            pattern.len() <= WORD_BITS,
+            "pattern too long for Bitap: {} symbols, maximum is {}",
+            pattern.len(),
+            WORD_BITS
+        );
+
+        let mut wildcard_bits = 0u64;
+        if let Some(w) = &wildcard {
+            for (j, c) in pattern.iter().enumerate() {
+                if c == w {
+                    wildcard_bits |= 1 << j;
+                }
+            }
+        }
+
+        let mut masks: HashMap<C, u64> = HashMap::new();
+        for (j, c) in pattern.iter().enumerate() {
+            if wildcard.as_ref() != Some(c) {
+                *masks.entry(c.clone()).or_insert(wildcard_bits) |= 1 << j;
+            }
+        }
+
+        Bitap {
+            pattern_len: pattern.len(),
+            masks,
+            wildcard_bits,
+        }
+    }
+
+    fn mask(&self, c: &C) -> u64 {
+        self.masks.get(c).cloned().unwrap_or(self.wildcard_bits)
+    }
+
+    /// Exact search (modulo the wildcard, if any): every returned position is the start of a
+    /// match.
+    pub fn match_<I: Iterator<Item = C>>(&self, text: I) -> Vec<usize> {
+        self.match_iter(text).collect()
+    }
+
+    pub fn match_iter<I: Iterator<Item = C>>(&self, text: I) -> MatchIter<'_, C, I> {
+        MatchIter {
+            bitap: self,
+            text: text.enumerate(),
+            r: 0,
+        }
+    }
+
+    /// Approximate search allowing up to `k` errors, following the Wu-Manber recurrence.
+    /// Returns `(start, mismatches)` pairs, where `mismatches` is the smallest number of
+    /// substitutions, insertions or deletions needed to turn the matched text into the pattern
+    /// (0 means an exact match). Since a match's length can then differ from the pattern's,
+    /// `start` is derived from the match's end position (end + 1 - pattern length, clamped at
+    /// 0), and may differ by up to `k` positions, in either direction, from where the matched
+    /// substring actually begins.
+    pub fn match_fuzzy<I: Iterator<Item = C>>(&self, text: I, k: usize) -> Vec<(usize, usize)> {
+        let last_bit = 1u64 << (self.pattern_len - 1);
+
+        // registers[d] = state register after allowing d mismatches, at the current position.
+        // Before any text is read, a prefix of the pattern up to `d` symbols long can be
+        // considered "matched" for free by deleting it, so registers[d] starts with its low `d`
+        // bits set rather than all-zero.
+        let mut registers: Vec<u64> = (0..=k).map(|d| (1u64 << d) - 1).collect();
+        // registers, as they were at the previous position.
+        let mut prev_registers = registers.clone();
+
+        let mut ret = vec![];
+
+        for (i, c) in text.enumerate() {
+            let mask = self.mask(&c);
+            prev_registers.clone_from(&registers);
+
+            registers[0] = ((prev_registers[0] << 1) | 1) & mask;
+            for d in 1..=k {
+                registers[d] = (((prev_registers[d] << 1) | 1) & mask)
+                    | ((prev_registers[d - 1] << 1) | 1)
+                    | prev_registers[d - 1]
+                    | (registers[d - 1] << 1);
+            }
+
+            if registers[k] & last_bit != 0 {
+                // `registers[d]` is a superset of `registers[d - 1]`'s matches, so the first `d`
+                // with the bit set is the true (minimal) mismatch count.
+                let mismatches = (0..=k).find(|&d| registers[d] & last_bit != 0).unwrap();
+                // With insertions/deletions allowed, a match can end before `pattern_len`
+                // characters of text have been consumed; clamp rather than underflow.
+                ret.push(((i + 1).saturating_sub(self.pattern_len), mismatches));
+            }
+        }
+
+        ret
+    }
+}
+
+pub struct MatchIter<'a, C: Eq + Hash + Clone, I: Iterator<Item = C>> {
+    bitap: &'a Bitap<C>,
+    text: Enumerate<I>,
+    r: u64,
+}
+
+impl<'a, C: Eq + Hash + Clone, I: Iterator<Item = C>> Iterator for MatchIter<'a, C, I> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let last_bit = 1u64 << (self.bitap.pattern_len - 1);
+        for (i, c) in &mut self.text {
+            let mask = self.bitap.mask(&c);
+            self.r = ((self.r << 1) | 1) & mask;
+            if self.r & last_bit != 0 {
+                return Some(i + 1 - self.bitap.pattern_len);
+            }
+        }
+        None
+    }
+}
+
+#[test]
+fn test_exact() {
+    let bitap: Bitap<char> = Bitap::new(&"aaba".chars().collect::<Vec<_>>(), None);
+    assert_eq!(
+        bitap.match_("aabaacaadaabaaba".chars()),
+        vec![0, 9, 12]
+    );
+
+    assert_eq!(bitap.match_("foo".chars()), Vec::<usize>::new());
+}
+
+#[test]
+fn test_iterator() {
+    let bitap: Bitap<char> = Bitap::new(&"aaba".chars().collect::<Vec<_>>(), None);
+    let mut iter = bitap.match_iter("aabaacaadaabaaba".chars());
+    assert_eq!(iter.next(), Some(0));
+    assert_eq!(iter.next(), Some(9));
+    assert_eq!(iter.next(), Some(12));
+    assert_eq!(iter.next(), None);
+}
+
+#[test]
+fn test_wildcard() {
+    // "a?a" matches "aXa" for any X.
+    let bitap: Bitap<char> = Bitap::new(&['a', '?', 'a'], Some('?'));
+    assert_eq!(bitap.match_("baxabaa".chars()), vec![1, 3]);
+}
+
+#[test]
+fn test_fuzzy() {
+    let bitap: Bitap<char> = Bitap::new(&"abc".chars().collect::<Vec<_>>(), None);
+
+    // Exact match: a (0, 0) result shows up as soon as the pattern fully occurs.
+    assert!(bitap.match_fuzzy("abc".chars(), 1).contains(&(0, 0)));
+
+    // "abd" is one substitution away from "abc".
+    assert!(bitap.match_fuzzy("abd".chars(), 1).contains(&(0, 1)));
+
+    // Three mismatches aren't found when only one is allowed...
+    assert_eq!(bitap.match_fuzzy("xyz".chars(), 1), Vec::new());
+    // ...but are within three.
+    assert!(bitap
+        .match_fuzzy("xyz".chars(), 3)
+        .iter()
+        .any(|&(_, mismatches)| mismatches == 3));
+}
+
+#[test]
+#[should_panic]
+fn test_pattern_too_long() {
+    let pat: Vec<char> = vec!['a'; WORD_BITS + 1];
+    Bitap::new(&pat, None);
+}
+
+// Minimal, dependency-free deterministic PRNG (xorshift64), so the property test below is
+// reproducible without pulling in a `rand` crate.
+#[cfg(test)]
+struct Xorshift64(u64);
+
+#[cfg(test)]
+impl Xorshift64 {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+// Brute-force oracle for `match_fuzzy`: `D[j][i]` is the edit distance (substitutions,
+// insertions into the text, deletions from the pattern) between `pattern[..j]` and some suffix
+// of `text[..i]` ending exactly at `i`. Returns, for each text position, the resulting cost of
+// matching the whole pattern there.
+#[cfg(test)]
+fn fuzzy_oracle(pattern: &[u8], text: &[u8]) -> Vec<usize> {
+    let (m, n) = (pattern.len(), text.len());
+    let mut d = vec![vec![0usize; n + 1]; m + 1];
+    for (j, row) in d.iter_mut().enumerate() {
+        row[0] = j;
+    }
+    for j in 1..=m {
+        for i in 1..=n {
+            let cost = usize::from(pattern[j - 1] != text[i - 1]);
+            d[j][i] = (d[j - 1][i - 1] + cost) // substitute/match
+                .min(d[j - 1][i] + 1) // delete from the pattern
+                .min(d[j][i - 1] + 1); // insert into the text
+        }
+    }
+    (0..=n).map(|i| d[m][i]).collect()
+}
+
+#[test]
+fn test_fuzzy_property() {
+    let alphabet = [b'a', b'b', b'c'];
+    let mut rng = Xorshift64(0x2545_F491_4F6C_DD1D);
+
+    for _ in 0..20_000 {
+        let pattern_len = 1 + (rng.next() % 10) as usize;
+        let text_len = (rng.next() % 15) as usize;
+        let k = (rng.next() % 6) as usize;
+        let pattern: Vec<u8> = (0..pattern_len)
+            .map(|_| alphabet[(rng.next() % alphabet.len() as u64) as usize])
+            .collect();
+        let text: Vec<u8> = (0..text_len)
+            .map(|_| alphabet[(rng.next() % alphabet.len() as u64) as usize])
+            .collect();
+
+        let costs = fuzzy_oracle(&pattern, &text);
+        let expected: Vec<(usize, usize)> = (0..text_len)
+            .filter(|&i| costs[i + 1] <= k)
+            .map(|i| ((i + 1).saturating_sub(pattern_len), costs[i + 1]))
+            .collect();
+
+        let bitap = Bitap::new(&pattern, None);
+        let got = bitap.match_fuzzy(text.iter().cloned(), k);
+        assert_eq!(
+            got, expected,
+            "pattern={pattern:?} text={text:?} k={k}"
+        );
+    }
+}
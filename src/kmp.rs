@@ -1,3 +1,5 @@
+use std::io::{self, Read};
+
 #[derive(Debug)]
 pub struct KMP<C> {
     pat: Vec<C>,
@@ -158,6 +160,88 @@ impl<'a, 'b, C: Eq, I: Iterator<Item = C>> Iterator for MatchIterator<'a, 'b, C,
     }
 }
 
+// Chunk size used by `stream_match` to read from the underlying `Read`.
+const STREAM_BUF_SIZE: usize = 64 * 1024;
+
+impl KMP<u8> {
+    /// Search a `Read` stream without buffering the whole input in memory. The pattern's match
+    /// state (`n_matched`) is carried across chunk boundaries, so matches spanning two chunks are
+    /// still found; reported positions are absolute byte offsets from the start of the stream.
+    pub fn stream_match<R: Read>(&self, r: R) -> StreamMatchIter<'_, R> {
+        StreamMatchIter {
+            pat: &self.pat,
+            pfx: &self.pfx,
+            reader: r,
+            buf: vec![0; STREAM_BUF_SIZE],
+            buf_len: 0,
+            buf_pos: 0,
+            chunk_start: 0,
+            n_matched: 0,
+            done: false,
+        }
+    }
+}
+
+pub struct StreamMatchIter<'a, R: Read> {
+    pat: &'a [u8],
+    pfx: &'a [usize],
+    reader: R,
+    buf: Vec<u8>,
+    buf_len: usize,
+    buf_pos: usize,
+
+    // Absolute offset of buf[0] in the stream.
+    chunk_start: usize,
+
+    n_matched: usize,
+    done: bool,
+}
+
+impl<'a, R: Read> Iterator for StreamMatchIter<'a, R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<io::Result<usize>> {
+        loop {
+            while self.buf_pos < self.buf_len {
+                let c = self.buf[self.buf_pos];
+                let i = self.chunk_start + self.buf_pos;
+                self.buf_pos += 1;
+
+                while self.n_matched > 0 && self.pat[self.n_matched] != c {
+                    self.n_matched = self.pfx[self.n_matched - 1];
+                }
+                if self.pat[self.n_matched] == c {
+                    self.n_matched += 1;
+                }
+                if self.n_matched == self.pat.len() {
+                    self.n_matched = self.pfx[self.pat.len() - 1];
+                    return Some(Ok(i + 1 - self.pat.len()));
+                }
+            }
+
+            if self.done {
+                return None;
+            }
+
+            match self.reader.read(&mut self.buf) {
+                Ok(0) => {
+                    self.done = true;
+                    return None;
+                }
+                Ok(n) => {
+                    self.chunk_start += self.buf_len;
+                    self.buf_len = n;
+                    self.buf_pos = 0;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
 #[test]
 fn test_kmp() {
     fn kmp(pat: &str, text: &str) -> Vec<usize> {
@@ -193,3 +277,30 @@ fn test_generic() {
     assert_eq!(iter.next(), Some(2));
     assert_eq!(iter.next(), None);
 }
+
+// A `Read` that only ever returns a few bytes per call, so tests can exercise matches that span
+// a chunk boundary without actually needing gigabytes of input.
+#[cfg(test)]
+struct TinyReads<'a>(&'a [u8]);
+
+#[cfg(test)]
+impl<'a> Read for TinyReads<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = 3.min(buf.len()).min(self.0.len());
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_stream_match() {
+    let kmp: KMP<u8> = KMP::new(b"aaba".to_vec());
+    let text = b"aabaacaadaabaaba";
+
+    let matches: Vec<usize> = kmp
+        .stream_match(TinyReads(text))
+        .collect::<io::Result<Vec<usize>>>()
+        .unwrap();
+    assert_eq!(matches, vec![0, 9, 12]);
+}